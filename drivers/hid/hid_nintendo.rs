@@ -2,41 +2,139 @@
 
 //! Nintendo Switch Controller Support
 
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
 use kernel::bindings;
 use kernel::c_str;
+use kernel::error::code::ETIMEDOUT;
+use kernel::hid::ff;
+use kernel::hid::input::RegisteredInputDevice;
+use kernel::hid::led::LedDevice;
 use kernel::hid::ConnectionRequest;
 use kernel::hid::DeviceKind;
 use kernel::hid::HidDeviceId;
 use kernel::module_hid_driver;
 use kernel::prelude::*;
+use kernel::sync::Completion;
 
 struct Nintendo;
 
 const PRODUCT_JOYCON: u32 = 0x2009;
 
+/// The largest report we expect to exchange during the handshake.
+const MAX_REPLY_LEN: usize = 64;
+
+/// Number of player-indicator LEDs.
+const NUM_PLAYER_LEDS: usize = 4;
+
+/// `led_index` used for the home-button light, distinct from the player LEDs' `0..NUM_PLAYER_LEDS`.
+const HOME_LED_INDEX: u32 = NUM_PLAYER_LEDS as u32;
+
 enum ControllerState {
     Init,
 }
 
+/// Shared state backing [`hid_send_sync`]: `raw_event` copies a matching reply into `buf` and
+/// signals `completion`; `hid_send_sync` blocks on `completion` and then reads it back out.
+///
+/// Only one synchronous exchange is ever in flight at a time (the controller's handshake is
+/// strictly sequential), so a single slot is enough.
+struct SyncReply {
+    /// The report ID `raw_event` should capture, or `0` if no reply is currently expected.
+    expected_report_id: AtomicU8,
+    /// The number of valid bytes in `buf`, set by `raw_event` before it signals `completion`.
+    len: AtomicUsize,
+    buf: UnsafeCell<[u8; MAX_REPLY_LEN]>,
+    completion: Completion,
+}
+
+// SAFETY: `buf` is written by `raw_event` only while `expected_report_id` is non-zero, and read
+// back by `hid_send_sync` only after `completion` fires, which happens-after that write.
+unsafe impl Sync for SyncReply {}
+
+impl SyncReply {
+    fn new() -> Self {
+        Self {
+            expected_report_id: AtomicU8::new(0),
+            len: AtomicUsize::new(0),
+            buf: UnsafeCell::new([0; MAX_REPLY_LEN]),
+            completion: Completion::new(),
+        }
+    }
+}
+
 struct Controller {
     state: ControllerState,
+    sync_reply: SyncReply,
+    /// Kept alive only so the force-feedback registration done in `probe` stays registered for
+    /// as long as the controller is.
+    _input: RegisteredInputDevice,
+    /// Kept alive only so the player-indicator LEDs registered in `probe` stay registered for as
+    /// long as the controller is.
+    _leds: [LedDevice; NUM_PLAYER_LEDS],
+    /// Kept alive only so the home-button light registered in `probe` stays registered for as
+    /// long as the controller is.
+    _home_led: LedDevice,
 }
 
-fn hid_send(dev: &kernel::hid::Device, data: &[u8]) -> Result {
-    let copied_data = Vec::try_with_capacity(data.len())?;
-    data.clone_into(&mut copied_data);
+fn hid_send(dev: &mut kernel::hid::Device, data: &[u8]) -> Result {
+    let mut copied_data = Vec::try_with_capacity(data.len())?;
+    copied_data.extend_from_slice(data)?;
     dev.hw_output_report(&mut copied_data)
 }
 
-fn hid_send_sync(dev: &kernel::hid::Device, data: &[u8], timeout: u32) -> Result {
+/// Sends `data` via `hw_output_report` and blocks until `raw_event` observes a reply whose
+/// report ID equals `expected_report_id`, copying it into `reply`. Sends up to `tries` times
+/// in all before giving up with `ETIMEDOUT`.
+///
+/// `controller` is taken separately from `dev` (rather than fetched internally via
+/// `dev.data::<Nintendo>()`) because a reference obtained that way borrows `dev`, which is also
+/// needed mutably here to send `data`.
+///
+/// Returns the number of bytes copied into `reply` on success.
+fn hid_send_sync(
+    dev: &mut kernel::hid::Device,
+    controller: &Controller,
+    data: &[u8],
+    reply: &mut [u8],
+    expected_report_id: u8,
+    timeout_ms: u64,
+) -> Result<usize> {
     let tries = 2;
 
     for _ in 0..tries {
+        // Discard any stale signal left over from a previous try (or a previous call) before we
+        // start waiting, so a reply that straggled in too late can't be mistaken for this one's.
+        controller.sync_reply.completion.reset();
+        controller
+            .sync_reply
+            .expected_report_id
+            .store(expected_report_id, Ordering::Release);
+
         // if this fails, return eagerly from the function
         hid_send(dev, data)?;
 
-        
+        if controller.sync_reply.completion.wait_timeout(timeout_ms) {
+            let len = controller.sync_reply.len.load(Ordering::Acquire).min(reply.len());
+
+            // SAFETY: `raw_event` wrote `len` bytes into `buf` before signalling `completion`,
+            // and that signal happens-before this read.
+            let src = unsafe { &(*controller.sync_reply.buf.get())[..len] };
+            reply[..len].copy_from_slice(src);
+
+            return Ok(len);
+        }
+
+        // This try timed out: stop matching so a reply that arrives from here on doesn't
+        // complete a future, unrelated wait.
+        controller
+            .sync_reply
+            .expected_report_id
+            .store(0, Ordering::Release);
     }
+
+    Err(ETIMEDOUT)
 }
 
 fn is_procon(dev: &kernel::hid::Device) -> bool {
@@ -44,17 +142,45 @@ fn is_procon(dev: &kernel::hid::Device) -> bool {
 }
 
 impl kernel::hid::Driver for Nintendo {
-    fn probe(dev: &mut kernel::hid::Device, id: &kernel::bindings::hid_device_id) -> Result {
+    type Data = Controller;
+
+    fn probe(
+        dev: &mut kernel::hid::Device,
+        id: &kernel::bindings::hid_device_id,
+    ) -> Result<Controller> {
         let name = dev.name();
-        let state = Box::try_new(Controller {
-            state: ControllerState::Init,
-        })?;
 
         pr_info!("{name}: probe!\n");
         dev.parse()?;
 
+        // Setting up the input and LED devices doesn't unblock any report I/O, so it's safe to
+        // do before `driver_data` is stashed (unlike `init`, below).
+        let mut input_dev = dev.new_input_device()?;
+        ff::register::<Nintendo>(&mut input_dev, dev)?;
+        let input_dev = input_dev.register()?;
+
+        let leds = [
+            LedDevice::register::<Nintendo>(dev, 0, "player1", 1)?,
+            LedDevice::register::<Nintendo>(dev, 1, "player2", 1)?,
+            LedDevice::register::<Nintendo>(dev, 2, "player3", 1)?,
+            LedDevice::register::<Nintendo>(dev, 3, "player4", 1)?,
+        ];
+        let home_led = LedDevice::register::<Nintendo>(dev, HOME_LED_INDEX, "home", 255)?;
+
+        Ok(Controller {
+            state: ControllerState::Init,
+            sync_reply: SyncReply::new(),
+            _input: input_dev,
+            _leds: leds,
+            _home_led: home_led,
+        })
+    }
+
+    fn init(dev: &mut kernel::hid::Device) -> Result {
+        let name = dev.name();
+
         pr_info!("{name}: dev.hw_start!\n");
-        dev.hw_start(ConnectionRequest::HidRaw.into())?;
+        dev.hw_start(ConnectionRequest::HidRaw | ConnectionRequest::FF)?;
 
         pr_info!("{name}: dev.hw_open!\n");
         dev.hw_open()?;
@@ -62,7 +188,19 @@ impl kernel::hid::Driver for Nintendo {
         pr_info!("{name}: dev.io_start\n");
         dev.io_start();
 
-        // try handshake :)
+        // SAFETY: `Nintendo` is the driver registered for this device, and `driver_data` is
+        // already in place by the time `init` runs (see `Adapter::probe_callback`), so any
+        // report that arrives from here on can be matched against it by `raw_event`.
+        // The pointer detaches `controller` from the borrow of `dev`, which `hid_send_sync`
+        // needs mutably.
+        let controller: *const Controller = unsafe { dev.data::<Nintendo>() };
+
+        // Handshake: ask the controller to identify itself (output report 0x80, subcommand
+        // 0x02) and wait for its reply (input report 0x81).
+        let mut reply = [0u8; MAX_REPLY_LEN];
+        // SAFETY: `driver_data` stays in place until `remove_callback` (or a failed `init`)
+        // reclaims it, neither of which can happen while `init` is still running.
+        hid_send_sync(dev, unsafe { &*controller }, &[0x80, 0x02], &mut reply, 0x81, 100)?;
 
         Ok(())
     }
@@ -72,6 +210,26 @@ impl kernel::hid::Driver for Nintendo {
         pr_info!("{name}: remove\n");
     }
 
+    fn play_effect(dev: &mut kernel::hid::Device, effect: ff::Effect) -> Result {
+        let name = dev.name();
+        pr_info!(
+            "{name}: play_effect strong={} weak={}\n",
+            effect.strong_magnitude,
+            effect.weak_magnitude
+        );
+
+        // TODO melse: translate into the Joy-Con's HD-rumble output report.
+        Ok(())
+    }
+
+    fn set_brightness(dev: &mut kernel::hid::Device, led_index: u32, brightness: u32) -> Result {
+        let name = dev.name();
+        pr_info!("{name}: set_brightness led={led_index} brightness={brightness}\n");
+
+        // TODO melse: translate into the Joy-Con's player-light/home-light output report.
+        Ok(())
+    }
+
     fn raw_event(
         dev: &mut kernel::hid::Device,
         hid_report: &kernel::bindings::hid_report,
@@ -79,6 +237,26 @@ impl kernel::hid::Driver for Nintendo {
     ) -> Result {
         let name = dev.name();
         let len = data.len();
+
+        // SAFETY: `Nintendo` is the driver registered for this device.
+        let controller = unsafe { dev.data::<Nintendo>() };
+
+        let report_id = data.first().copied().unwrap_or(0);
+        let expected = controller.sync_reply.expected_report_id.load(Ordering::Acquire);
+        if expected != 0 && expected == report_id {
+            let copy_len = data.len().min(MAX_REPLY_LEN);
+
+            // SAFETY: `hid_send_sync` only reads `buf` after `completion` fires, and we have
+            // not signalled it yet, so we are the sole writer here.
+            unsafe { (*controller.sync_reply.buf.get())[..copy_len].copy_from_slice(&data[..copy_len]) };
+            controller.sync_reply.len.store(copy_len, Ordering::Release);
+            controller
+                .sync_reply
+                .expected_report_id
+                .store(0, Ordering::Release);
+            controller.sync_reply.completion.complete();
+        }
+
         pr_info!("{name}: raw_event! {len}\n");
         Ok(())
     }
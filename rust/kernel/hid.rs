@@ -5,14 +5,19 @@
 //! C header: [`include/linux/hid.h`](../../../../include/linux/hid.h)
 
 use core::ops::BitOr;
-use core::slice::from_raw_parts;
+use core::slice::{from_raw_parts, from_raw_parts_mut};
 
 use bindings::hid_report;
 
+pub mod ff;
+pub mod input;
+pub mod led;
+
 use crate::{
     bindings, device, driver,
     error::{from_kernel_result, Result},
     pr_info, pr_warn,
+    prelude::Box,
     str::CStr,
     to_result, ThisModule,
 };
@@ -36,13 +41,14 @@ impl<T: Driver> driver::DriverOps for Adapter<T> {
         hid.probe = Some(Self::probe_callback);
         hid.remove = Some(Self::remove_callback);
         hid.raw_event = Some(Self::raw_event_callback);
+        hid.report_fixup = Some(Self::report_fixup_callback);
         hid.id_table = T::ID_TABLE.as_ptr();
 
         // SAFETY:
         //   - `reg` lives at least until the call to `hid_unregister_driver()` returns.
         //   - `name` pointer has static lifetime.
         //   - `module.0` lives at least as long as the module.
-        //   - `probe()`, `remove()`, and `raw_event`  are static functions.
+        //   - `probe()`, `remove()`, `raw_event`, and `report_fixup` are static functions.
         //   - `id_table` is a raw pointer with static lifetime ,as guaranteed by the type of [`driver::ID_TABLE`]
         to_result(unsafe { bindings::__hid_register_driver(reg, module.0, name.as_char_ptr()) })
     }
@@ -70,7 +76,28 @@ impl<T: Driver> Adapter<T> {
                 // `id` only lives for the duration of this function call.
                 let id = unsafe { raw_id.as_ref().unwrap() };
 
-                T::probe(&mut dev, id)?;
+                let data = T::probe(&mut dev, id)?;
+                let data = Box::try_new(data)?;
+                let data = Box::into_raw(data);
+
+                // SAFETY: `hid` is valid by the contract with the C code. This must happen
+                // before `T::init` runs below (rather than after, once `probe` has fully
+                // finished) because `T::init` is where I/O gets unblocked; any report arriving
+                // from that point on reaches `raw_event_callback` -> `Device::data`, which reads
+                // `driver_data` back out. The pointer stashed here is reclaimed (and dropped) by
+                // `remove_callback` on success, or below on failure.
+                unsafe { bindings::hid_set_drvdata(hid, data as *mut core::ffi::c_void) };
+
+                if let Err(e) = T::init(&mut dev) {
+                    // `T::init` failed, so this probe is failing overall and `remove_callback`
+                    // will never run for it: reclaim what was just stashed above ourselves.
+                    //
+                    // SAFETY: `data` was produced by `Box::into_raw` above and has not been
+                    // reclaimed yet.
+                    drop(unsafe { Box::from_raw(data) });
+                    return Err(e);
+                }
+
                 Ok(0)
         }
     }
@@ -81,6 +108,12 @@ impl<T: Driver> Adapter<T> {
         // remain alive for the lifetime of `hid`.
         let mut dev = unsafe { Device::from_ptr(hid) };
         T::remove(&mut dev);
+
+        // SAFETY: `driver_data` was set in `probe_callback` to a pointer obtained from
+        // `Box::into_raw` on a `Box<T::Data>`. `remove_callback` only runs after a successful
+        // probe, so `probe_callback`'s failure path has not reclaimed it.
+        let data = unsafe { Box::from_raw(bindings::hid_get_drvdata(hid) as *mut T::Data) };
+        drop(data);
     }
 
     extern "C" fn raw_event_callback(
@@ -109,6 +142,28 @@ impl<T: Driver> Adapter<T> {
             Ok(0)
         }
     }
+
+    extern "C" fn report_fixup_callback(
+        hid: *mut bindings::hid_device,
+        buf: *mut u8,
+        size: *mut core::ffi::c_uint,
+    ) -> *mut u8 {
+        // SAFETY: `hid` is valid by the contract with the C code. `dev` is
+        // alive only for the duration of this call, so it is guaranteed to
+        // remain alive for the lifetime of `hid`.
+        let mut dev = unsafe { Device::from_ptr(hid) };
+
+        // SAFETY: `buf` and `size` are valid by the contract with the C code: `buf` points to
+        // `*size` initialized bytes, both alive for at least the duration of this call.
+        let rdesc = unsafe { from_raw_parts_mut(buf, *size as usize) };
+
+        let rdesc = T::report_fixup(&mut dev, rdesc);
+
+        // SAFETY: `size` is valid for writes by the contract with the C code.
+        unsafe { *size = rdesc.len() as core::ffi::c_uint };
+
+        rdesc.as_mut_ptr()
+    }
 }
 
 /// An HID device
@@ -132,6 +187,22 @@ pub enum ConnectionRequest {
     Driver = 6,
 }
 
+/// The kind of report targeted by a `hid_hw_raw_request` (the `rtype` passed to that function).
+#[derive(Clone, Copy, Debug)]
+enum ReportType {
+    Input,
+    Feature,
+}
+
+impl ReportType {
+    const fn as_raw(self) -> core::ffi::c_uchar {
+        (match self {
+            Self::Input => bindings::HID_INPUT_REPORT,
+            Self::Feature => bindings::HID_FEATURE_REPORT,
+        }) as core::ffi::c_uchar
+    }
+}
+
 pub struct ConnectionMask(core::ffi::c_uint);
 
 const fn bit(x: core::ffi::c_uint) -> core::ffi::c_uint {
@@ -144,27 +215,27 @@ impl From<ConnectionRequest> for ConnectionMask {
     }
 }
 
-// impl BitOr for ConnectionRequest {
-//     type Output = ConnectionMask;
+impl BitOr for ConnectionRequest {
+    type Output = ConnectionMask;
 
-//     fn bitor(self, rhs: Self) -> Self::Output {
-//         ConnectionMask(bit(self as core::ffi::c_uint) | bit(rhs as core::ffi::c_uint))
-//     }
-// }
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ConnectionMask(bit(self as core::ffi::c_uint) | bit(rhs as core::ffi::c_uint))
+    }
+}
 
-// impl BitOr<ConnectionMask> for ConnectionRequest {
-//     type Output = ConnectionMask;
+impl BitOr<ConnectionMask> for ConnectionRequest {
+    type Output = ConnectionMask;
 
-//     fn bitor(self, rhs: ConnectionMask) -> Self::Output {
-//         ConnectionMask(bit(self as core::ffi::c_uint) | bit(rhs.0))
-//     }
-// }
+    fn bitor(self, rhs: ConnectionMask) -> Self::Output {
+        ConnectionMask(bit(self as core::ffi::c_uint) | rhs.0)
+    }
+}
 
 impl BitOr for ConnectionMask {
     type Output = ConnectionMask;
 
     fn bitor(self, rhs: ConnectionMask) -> Self::Output {
-        ConnectionMask(bit(self.0) | bit(rhs.0))
+        ConnectionMask(self.0 | rhs.0)
     }
 }
 
@@ -186,6 +257,11 @@ impl Device {
         unsafe { CStr::from_char_ptr((*self.ptr).name.as_ptr()) }
     }
 
+    /// Returns the raw `hid_device` pointer backing this device.
+    pub(crate) fn as_raw(&self) -> *mut bindings::hid_device {
+        self.ptr
+    }
+
     pub fn parse(&self) -> Result {
         // TODO melse: was hid_parse??
         to_result(unsafe { bindings::hid_open_report(self.ptr) })
@@ -224,6 +300,85 @@ impl Device {
         let buf = buf.as_mut_ptr();
         to_result(unsafe { bindings::hid_hw_output_report(self.ptr, buf, len) })
     }
+
+    /// Issues a `hid_hw_raw_request` that reads the device's current feature report `report_id`
+    /// into `buf`. Returns the number of bytes transferred.
+    pub fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.hw_raw_request(
+            report_id,
+            buf,
+            ReportType::Feature,
+            bindings::HID_REQ_GET_REPORT,
+        )
+    }
+
+    /// Issues a `hid_hw_raw_request` that sets feature report `report_id` to the contents of
+    /// `buf`. Returns the number of bytes transferred.
+    pub fn send_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.hw_raw_request(
+            report_id,
+            buf,
+            ReportType::Feature,
+            bindings::HID_REQ_SET_REPORT,
+        )
+    }
+
+    /// Issues a `hid_hw_raw_request` that reads input report `report_id` into `buf`. Returns the
+    /// number of bytes transferred.
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.hw_raw_request(
+            report_id,
+            buf,
+            ReportType::Input,
+            bindings::HID_REQ_GET_REPORT,
+        )
+    }
+
+    fn hw_raw_request(
+        &mut self,
+        report_id: u8,
+        buf: &mut [u8],
+        report_type: ReportType,
+        request_type: u32,
+    ) -> Result<usize> {
+        let len = buf.len();
+        let buf = buf.as_mut_ptr();
+
+        // SAFETY: `self.ptr` is non-null and valid by the type invariants. `buf` is valid for
+        // `len` bytes for the duration of this call.
+        let ret = unsafe {
+            bindings::hid_hw_raw_request(
+                self.ptr,
+                report_id,
+                buf,
+                len,
+                report_type.as_raw(),
+                request_type as core::ffi::c_int,
+            )
+        };
+
+        to_result(ret)?;
+        Ok(ret as usize)
+    }
+
+    /// Allocates a new, unregistered [`input::InputDevice`] as a child of this HID device.
+    pub fn new_input_device(&self) -> Result<input::InputDevice> {
+        input::InputDevice::new(self)
+    }
+
+    /// Returns a reference to the per-device data stored by `T::probe`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `T` is the driver that was registered for this device (i.e. the
+    /// same `T` whose `probe` produced the data currently stored in `driver_data`).
+    pub unsafe fn data<T: Driver>(&self) -> &T::Data {
+        // SAFETY: By the safety requirements of this function, `driver_data` was set by
+        // `Adapter::<T>::probe_callback` to a valid `Box::into_raw(Box<T::Data>)`, and is not
+        // reclaimed until `remove_callback` runs (or `probe_callback` unwinds a failed
+        // `T::init`), which cannot race with this call.
+        unsafe { &*(bindings::hid_get_drvdata(self.ptr) as *const T::Data) }
+    }
 }
 
 // TODO melse: this doesn't actually help :( think about how we can represent the lifetime of a device in rust somehow
@@ -314,14 +469,61 @@ pub trait Driver {
     /// matching the table provided are connected, `probe` will be called.
     const ID_TABLE: &'static [bindings::hid_device_id];
 
+    /// Per-device state created by `probe` and made available to `raw_event` and `remove` via
+    /// [`Device::data`].
+    type Data;
+
     /// Called when a new device is inserted.
-    fn probe(dev: &mut Device, id: &bindings::hid_device_id) -> Result;
+    ///
+    /// The value returned is stored in the device's `driver_data` and can be retrieved from
+    /// `raw_event` and `remove` with [`Device::data`]. `probe` must not unblock real report I/O
+    /// (no `hw_start`/`hw_open`/[`Device::io_start`]) — `driver_data` is not yet populated while
+    /// `probe` runs, so a report arriving that early would crash [`Device::data`]. Do that in
+    /// [`Self::init`] instead, which runs right after `driver_data` has been stored.
+    fn probe(dev: &mut Device, id: &bindings::hid_device_id) -> Result<Self::Data>;
+
+    /// Called immediately after the `Self::Data` returned by `probe` has been stored and is
+    /// reachable from `raw_event`/`remove` via [`Device::data`].
+    ///
+    /// This is where a driver should unblock real report I/O — `hw_start`, `hw_open`,
+    /// [`Device::io_start`], or a synchronous handshake built on them — since only from this
+    /// point on is it safe for an incoming report to look up this device's per-device state.
+    ///
+    /// The default implementation does nothing.
+    fn init(_dev: &mut Device) -> Result {
+        Ok(())
+    }
 
     /// Called when a device is removed.
     fn remove(dev: &mut Device);
 
     /// Called when an HID report arrives.
     fn raw_event(dev: &mut Device, hid_report: &bindings::hid_report, data: &[u8]) -> Result;
+
+    /// Called before `rdesc` is parsed by `hid_open_report`, letting a driver patch a broken
+    /// report descriptor in place. The returned slice replaces `rdesc`; it may be `rdesc` itself
+    /// (the default), a sub-slice of it, or a driver-owned buffer that outlives this call.
+    fn report_fixup<'a>(_dev: &mut Device, rdesc: &'a mut [u8]) -> &'a mut [u8] {
+        rdesc
+    }
+
+    /// Called when the input core delivers a force-feedback effect uploaded via [`ff::register`].
+    ///
+    /// The default implementation rejects all effects; drivers that register for FF support must
+    /// override this to translate `effect` into the controller's vibration output report.
+    fn play_effect(_dev: &mut Device, _effect: ff::Effect) -> Result {
+        Err(crate::error::code::EOPNOTSUPP)
+    }
+
+    /// Called when userspace (or the driver itself) sets the brightness of an LED registered via
+    /// [`led::LedDevice::register`].
+    ///
+    /// The default implementation rejects all brightness changes; drivers that register LEDs
+    /// must override this to translate `value` into the controller's LED-configuration output
+    /// report.
+    fn set_brightness(_dev: &mut Device, _led_index: u32, _value: u32) -> Result {
+        Err(crate::error::code::EOPNOTSUPP)
+    }
 }
 
 /// Define an HID driver module.
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Force-feedback (rumble) support, built on the input core's FF upload machinery.
+//!
+//! C header: [`include/linux/input.h`](../../../../../include/linux/input.h)
+
+use crate::{
+    bindings,
+    error::{code::EINVAL, from_kernel_result},
+    hid::{input::InputDevice, Device, Driver},
+    to_result, Result,
+};
+
+/// A normalized force-feedback effect, decoded from the kernel's `struct ff_effect`.
+///
+/// Only rumble effects (`FF_RUMBLE`) are represented; other effect types are rejected before a
+/// driver ever sees them.
+#[derive(Clone, Copy, Debug)]
+pub struct Effect {
+    /// The strong (low-frequency) motor's magnitude, in `0..=0xffff`.
+    pub strong_magnitude: u16,
+    /// The weak (high-frequency) motor's magnitude, in `0..=0xffff`.
+    pub weak_magnitude: u16,
+    /// How long the effect should play for, in milliseconds. `0` means "play indefinitely, until
+    /// replaced or stopped".
+    pub duration_ms: u16,
+}
+
+impl Effect {
+    fn from_raw(effect: &bindings::ff_effect) -> Option<Self> {
+        if effect.type_ != bindings::FF_RUMBLE as u16 {
+            return None;
+        }
+
+        // SAFETY: `effect.type_ == FF_RUMBLE`, so the `rumble` variant of this union is the
+        // active one.
+        let rumble = unsafe { effect.u.rumble };
+
+        Some(Self {
+            strong_magnitude: rumble.strong_magnitude,
+            weak_magnitude: rumble.weak_magnitude,
+            duration_ms: effect.replay.length,
+        })
+    }
+}
+
+/// Enables rumble support on `input_dev`, delivering uploaded effects to `T::play_effect`.
+///
+/// `hid` is passed back to `T::play_effect` so it can reach its per-device state via
+/// [`Device::data`]. Must be called before [`InputDevice::register`].
+pub fn register<T: Driver>(input_dev: &mut InputDevice, hid: &Device) -> Result {
+    // SAFETY: `input_dev.as_raw()` is valid for the lifetime of `input_dev`.
+    unsafe {
+        bindings::input_set_capability(
+            input_dev.as_raw(),
+            bindings::EV_FF as u16,
+            bindings::FF_RUMBLE as u16,
+        )
+    };
+
+    // SAFETY: `input_dev.as_raw()` is valid and not yet registered. `hid.as_raw()` is valid for
+    // at least as long as `input_dev` (both are owned by the same HID device's `probe`), and is
+    // handed back unchanged as the opaque `data` pointer in `play_effect_callback::<T>`.
+    to_result(unsafe {
+        bindings::input_ff_create_memless(
+            input_dev.as_raw(),
+            hid.as_raw().cast(),
+            Some(play_effect_callback::<T>),
+        )
+    })
+}
+
+extern "C" fn play_effect_callback<T: Driver>(
+    _input_dev: *mut bindings::input_dev,
+    data: *mut core::ffi::c_void,
+    effect: *mut bindings::ff_effect,
+) -> core::ffi::c_int {
+    from_kernel_result! {
+        // SAFETY: `data` is the `hid_device` pointer stashed by `register` above, valid for as
+        // long as the input device it was registered alongside.
+        let mut dev = unsafe { Device::from_ptr(data.cast()) };
+
+        // SAFETY: `effect` is valid by the contract with the C code, and lives for the duration
+        // of this call.
+        let effect = unsafe { effect.as_ref().unwrap() };
+        let effect = Effect::from_raw(effect).ok_or(EINVAL)?;
+
+        T::play_effect(&mut dev, effect)?;
+
+        Ok(0)
+    }
+}
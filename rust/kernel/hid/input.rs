@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Linux input devices, for reporting decoded HID events (buttons, axes, etc.) to userspace.
+//!
+//! C header: [`include/linux/input.h`](../../../../../include/linux/input.h)
+
+use core::ptr::NonNull;
+
+use crate::{bindings, device::RawDevice, error::code::ENOMEM, to_result, Result};
+
+/// Which kind of event a capability, or a report call, concerns.
+#[derive(Clone, Copy, Debug)]
+pub enum EventType {
+    /// A button or key (`EV_KEY`).
+    Key,
+    /// An absolute axis (`EV_ABS`).
+    Abs,
+}
+
+impl EventType {
+    const fn as_raw(self) -> u32 {
+        match self {
+            Self::Key => bindings::EV_KEY,
+            Self::Abs => bindings::EV_ABS,
+        }
+    }
+}
+
+/// A not-yet-registered Linux input device (`struct input_dev`).
+///
+/// Build up the device's capabilities with [`Self::set_capability`] and [`Self::set_abs_params`],
+/// then call [`Self::register`] to publish it to userspace. Dropping an unregistered
+/// `InputDevice` frees it.
+pub struct InputDevice {
+    ptr: *mut bindings::input_dev,
+}
+
+impl InputDevice {
+    /// Allocates a new input device as a child of `parent`.
+    pub fn new(parent: &dyn RawDevice) -> Result<Self> {
+        // SAFETY: FFI call with no special requirements, the result is checked for null below.
+        let ptr = unsafe { bindings::input_allocate_device() };
+        let ptr = NonNull::new(ptr).ok_or(ENOMEM)?.as_ptr();
+
+        // SAFETY: `ptr` was just allocated above and is not yet shared with anyone else.
+        unsafe { (*ptr).dev.parent = parent.raw_device() };
+
+        Ok(Self { ptr })
+    }
+
+    /// Returns the raw `input_dev` pointer backing this device.
+    pub(crate) fn as_raw(&self) -> *mut bindings::input_dev {
+        self.ptr
+    }
+
+    /// Declares that this device can produce events of kind `ev_type` with the given `code`
+    /// (e.g. a particular button or axis).
+    pub fn set_capability(&mut self, ev_type: EventType, code: u32) {
+        // SAFETY: `self.ptr` is valid for the lifetime of `self`.
+        unsafe { bindings::input_set_capability(self.ptr, ev_type.as_raw() as u16, code as u16) };
+    }
+
+    /// Configures the range, fuzz and flat values for absolute axis `code`.
+    ///
+    /// Implies [`Self::set_capability`]`(`[`EventType::Abs`]`, code)`.
+    pub fn set_abs_params(&mut self, code: u32, min: i32, max: i32, fuzz: i32, flat: i32) {
+        // SAFETY: `self.ptr` is valid for the lifetime of `self`.
+        unsafe { bindings::input_set_abs_params(self.ptr, code, min, max, fuzz, flat) };
+    }
+
+    /// Registers the device with the input core, making it visible to userspace.
+    ///
+    /// On success, the returned [`RegisteredInputDevice`] unregisters the device when dropped.
+    pub fn register(self) -> Result<RegisteredInputDevice> {
+        // SAFETY: `self.ptr` was allocated by `input_allocate_device` and fully configured by the
+        // calls above.
+        to_result(unsafe { bindings::input_register_device(self.ptr) })?;
+
+        let ptr = self.ptr;
+        // The device is now owned by `RegisteredInputDevice`; don't free it on drop.
+        core::mem::forget(self);
+        Ok(RegisteredInputDevice { ptr })
+    }
+}
+
+impl Drop for InputDevice {
+    fn drop(&mut self) {
+        // SAFETY: `self.ptr` was allocated by `input_allocate_device` and never registered (a
+        // registered device is represented by `RegisteredInputDevice` instead, which doesn't run
+        // this impl).
+        unsafe { bindings::input_free_device(self.ptr) };
+    }
+}
+
+/// An [`InputDevice`] that has been registered with the input core.
+///
+/// Dropping this unregisters the device.
+pub struct RegisteredInputDevice {
+    ptr: *mut bindings::input_dev,
+}
+
+impl RegisteredInputDevice {
+    /// Reports a key/button event.
+    pub fn report_key(&self, code: u32, pressed: bool) {
+        // SAFETY: `self.ptr` is valid for the lifetime of `self`.
+        unsafe {
+            bindings::input_report_key(
+                self.ptr,
+                code as core::ffi::c_uint,
+                pressed as core::ffi::c_int,
+            )
+        };
+    }
+
+    /// Reports an absolute axis event.
+    pub fn report_abs(&self, code: u32, value: i32) {
+        // SAFETY: `self.ptr` is valid for the lifetime of `self`.
+        unsafe { bindings::input_report_abs(self.ptr, code as core::ffi::c_uint, value) };
+    }
+
+    /// Flushes buffered events to userspace as a single `EV_SYN` report.
+    pub fn sync(&self) {
+        // SAFETY: `self.ptr` is valid for the lifetime of `self`.
+        unsafe { bindings::input_sync(self.ptr) };
+    }
+}
+
+// SAFETY: the input core synchronizes access to `struct input_dev` internally.
+unsafe impl Send for RegisteredInputDevice {}
+// SAFETY: the reporting methods above go through the kernel's own synchronization.
+unsafe impl Sync for RegisteredInputDevice {}
+
+impl Drop for RegisteredInputDevice {
+    fn drop(&mut self) {
+        // SAFETY: `self.ptr` was registered by `InputDevice::register` and is not accessed again
+        // after this point.
+        unsafe { bindings::input_unregister_device(self.ptr) };
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! LED class devices, for exposing per-controller indicators (player LEDs, the home button
+//! light, etc.) to userspace via `/sys/class/leds`.
+//!
+//! C header: [`include/linux/leds.h`](../../../../../include/linux/leds.h)
+
+use crate::{
+    bindings,
+    device::RawDevice,
+    error::from_kernel_result,
+    hid::{Device, Driver},
+    prelude::Box,
+    to_result, Result,
+};
+
+/// Max length (including the NUL terminator) of a `led_classdev`'s name.
+const MAX_NAME_LEN: usize = 64;
+
+fn build_name(prefix: &[u8], suffix: &[u8]) -> [u8; MAX_NAME_LEN] {
+    let mut name = [0u8; MAX_NAME_LEN];
+    let mut pos = 0;
+
+    for part in [prefix, b"::", suffix] {
+        let len = part.len().min(MAX_NAME_LEN - 1 - pos);
+        name[pos..pos + len].copy_from_slice(&part[..len]);
+        pos += len;
+    }
+
+    name
+}
+
+/// The `led_classdev` plus the extra state `brightness_set_callback` needs to route a brightness
+/// change back to `T::set_brightness`.
+///
+/// `classdev` must remain the first field: the C callback only receives a `led_classdev`
+/// pointer, so we recover the rest of this struct from it the same way `container_of` would.
+#[repr(C)]
+struct RawLed {
+    classdev: bindings::led_classdev,
+    name: [u8; MAX_NAME_LEN],
+    hid: *mut bindings::hid_device,
+    led_index: u32,
+}
+
+/// A registered LED class device (one player indicator, or the home button light, etc.).
+///
+/// Dropping this unregisters the LED.
+pub struct LedDevice {
+    raw: Box<RawLed>,
+}
+
+impl LedDevice {
+    /// Registers a new LED named `"<hid device name>::<name_suffix>"`, with brightness changes
+    /// routed to `T::set_brightness(dev, led_index, value)`.
+    pub fn register<T: Driver>(
+        hid: &Device,
+        led_index: u32,
+        name_suffix: &str,
+        max_brightness: u32,
+    ) -> Result<Self> {
+        let mut raw = Box::try_new(RawLed {
+            // SAFETY: a zeroed `led_classdev` is a valid initial value; every field the LED core
+            // relies on is set explicitly below, before registration.
+            classdev: unsafe { core::mem::zeroed() },
+            name: build_name(hid.name().as_bytes(), name_suffix.as_bytes()),
+            hid: hid.as_raw(),
+            led_index,
+        })?;
+
+        raw.classdev.name = raw.name.as_ptr().cast();
+        raw.classdev.max_brightness = max_brightness as core::ffi::c_int;
+        raw.classdev.brightness_set_blocking = Some(brightness_set_callback::<T>);
+
+        // SAFETY: `&mut raw.classdev` is valid for the lifetime of this call, and `hid.raw_device()`
+        // is valid for the lifetime of `hid`.
+        to_result(unsafe {
+            bindings::led_classdev_register(hid.raw_device(), &mut raw.classdev)
+        })?;
+
+        Ok(Self { raw })
+    }
+}
+
+impl Drop for LedDevice {
+    fn drop(&mut self) {
+        // SAFETY: `self.raw.classdev` was registered by `Self::register` and is not accessed
+        // again after this point.
+        unsafe { bindings::led_classdev_unregister(&mut self.raw.classdev) };
+    }
+}
+
+extern "C" fn brightness_set_callback<T: Driver>(
+    led_cdev: *mut bindings::led_classdev,
+    brightness: bindings::led_brightness,
+) -> core::ffi::c_int {
+    from_kernel_result! {
+        // SAFETY: `led_cdev` always points at the `classdev` field of a `RawLed`, which (being
+        // `#[repr(C)]` with `classdev` as its first field) starts at the same address.
+        let raw = unsafe { &*(led_cdev as *const RawLed) };
+
+        // SAFETY: `raw.hid` was captured from a valid `Device` in `register`, and remains valid
+        // for as long as the LED stays registered, which outlives this call.
+        let mut dev = unsafe { Device::from_ptr(raw.hid) };
+
+        T::set_brightness(&mut dev, raw.led_index, brightness as u32)?;
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Synchronization primitives.
+//!
+//! C header: [`include/linux/completion.h`](../../../../include/linux/completion.h)
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use crate::bindings;
+
+/// A wrapper around the kernel's `struct completion`.
+///
+/// A `Completion` lets one context block (with an optional timeout) until another context
+/// signals that some work is done, e.g. waiting for a synchronous command/response exchange to
+/// receive its reply.
+pub struct Completion(UnsafeCell<MaybeUninit<bindings::completion>>);
+
+// SAFETY: `Completion` is just a kernel `struct completion`, which the kernel itself allows to
+// be waited on and completed from any context.
+unsafe impl Send for Completion {}
+// SAFETY: all of the methods below go through the kernel's own synchronization; there is no
+// unsynchronized access to the inner `UnsafeCell`.
+unsafe impl Sync for Completion {}
+
+impl Completion {
+    /// Creates a new completion, in the "not yet completed" state.
+    pub fn new() -> Self {
+        let mut raw = MaybeUninit::uninit();
+
+        // SAFETY: `raw` is valid for writes of a `bindings::completion`.
+        unsafe { bindings::init_completion(raw.as_mut_ptr()) };
+
+        Self(UnsafeCell::new(raw))
+    }
+
+    fn as_ptr(&self) -> *mut bindings::completion {
+        self.0.get().cast()
+    }
+
+    /// Signals the completion, waking up a single waiter blocked in [`Self::wait_timeout`].
+    pub fn complete(&self) {
+        // SAFETY: `self.as_ptr()` was initialized by `new` and is valid for the lifetime of
+        // `self`.
+        unsafe { bindings::complete(self.as_ptr()) };
+    }
+
+    /// Resets the completion to the "not yet completed" state, discarding any previous signal.
+    ///
+    /// Callers that reuse a `Completion` across multiple wait/signal rounds (e.g. retrying a
+    /// timed-out exchange) must call this before each wait; otherwise a signal left over from a
+    /// stale round would make the next, unrelated wait return immediately.
+    pub fn reset(&self) {
+        // SAFETY: `self.as_ptr()` was initialized by `new` and is valid for the lifetime of
+        // `self`.
+        unsafe { bindings::reinit_completion(self.as_ptr()) };
+    }
+
+    /// Blocks until the completion is signalled, or `timeout_ms` milliseconds have elapsed.
+    ///
+    /// Returns `true` if the completion was signalled in time, `false` on timeout.
+    pub fn wait_timeout(&self, timeout_ms: u64) -> bool {
+        // SAFETY: `timeout_ms` is a plain integer conversion.
+        let jiffies = unsafe { bindings::msecs_to_jiffies(timeout_ms as core::ffi::c_uint) };
+
+        // SAFETY: `self.as_ptr()` was initialized by `new` and is valid for the lifetime of
+        // `self`.
+        let remaining = unsafe { bindings::wait_for_completion_timeout(self.as_ptr(), jiffies) };
+
+        remaining != 0
+    }
+}
+
+impl Default for Completion {
+    fn default() -> Self {
+        Self::new()
+    }
+}